@@ -1,5 +1,5 @@
 use colored::Colorize;
-use std::{cmp::max, collections::HashMap, fs};
+use std::{cmp::max, fs, io::Read};
 
 #[derive(Debug)]
 enum JSONParseError {
@@ -7,8 +7,22 @@ enum JSONParseError {
     NotFound,
     UnexpectedChar(usize),
     MissingClosing(usize),
+    DuplicateKey(String),
+    Io(String),
+    DepthExceeded(usize),
 }
 
+// how deeply array()/object() may nest before parsing gives up, so that
+// adversarial input like `[[[[...]]]]` can't blow the call stack.
+//
+// this guard only covers the recursion-depth DoS: a single short,
+// top-level number literal with an oversized digit string (e.g.
+// `99999999999999999999`) is a separate, cheaper DoS vector that this
+// depth limit does nothing for, since it never recurses. That one is
+// guarded in integer()/exponent() (and their reader_* counterparts)
+// directly, by saturating instead of panicking on overflow.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
 #[derive(Debug, Clone, PartialEq)]
 enum JSONValue {
     Null,
@@ -17,7 +31,198 @@ enum JSONValue {
     Number(f64),
     String(String),
     Array(Vec<JSONValue>),
-    Object(HashMap<String, JSONValue>),
+    Object(Vec<(String, JSONValue)>),
+    // a `{{ expression }}` template placeholder, only produced when parsing
+    // with `allow_expr_placeholders` enabled; holds the trimmed expression text
+    Expr(String),
+}
+
+impl JSONValue {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            JSONValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            JSONValue::True => Some(true),
+            JSONValue::False => Some(false),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            JSONValue::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[JSONValue]> {
+        match self {
+            JSONValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn as_object(&self) -> Option<&[(String, JSONValue)]> {
+        match self {
+            JSONValue::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&JSONValue> {
+        self.as_object()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    // consume self and convert it into `T`, panicking if the variant doesn't match.
+    // prefer the non-panicking `as_*`/`get` accessors, or `T::try_from(value)` directly,
+    // when the shape of the document isn't already guaranteed by the caller
+    fn unwrap<T>(self) -> T
+    where
+        T: TryFrom<JSONValue>,
+        T::Error: std::fmt::Debug,
+    {
+        T::try_from(self).unwrap()
+    }
+}
+
+impl std::ops::Index<usize> for JSONValue {
+    type Output = JSONValue;
+
+    fn index(&self, index: usize) -> &JSONValue {
+        &self.as_array().expect("not a JSONValue::Array")[index]
+    }
+}
+
+impl TryFrom<JSONValue> for f64 {
+    type Error = JSONValue;
+
+    fn try_from(value: JSONValue) -> Result<Self, Self::Error> {
+        match value {
+            JSONValue::Number(n) => Ok(n),
+            other => Err(other),
+        }
+    }
+}
+
+impl TryFrom<JSONValue> for String {
+    type Error = JSONValue;
+
+    fn try_from(value: JSONValue) -> Result<Self, Self::Error> {
+        match value {
+            JSONValue::String(s) => Ok(s),
+            other => Err(other),
+        }
+    }
+}
+
+impl TryFrom<JSONValue> for bool {
+    type Error = JSONValue;
+
+    fn try_from(value: JSONValue) -> Result<Self, Self::Error> {
+        match value {
+            JSONValue::True => Ok(true),
+            JSONValue::False => Ok(false),
+            other => Err(other),
+        }
+    }
+}
+
+impl TryFrom<JSONValue> for Vec<JSONValue> {
+    type Error = JSONValue;
+
+    fn try_from(value: JSONValue) -> Result<Self, Self::Error> {
+        match value {
+            JSONValue::Array(a) => Ok(a),
+            other => Err(other),
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+impl TryFrom<JSONValue> for Vec<(String, JSONValue)> {
+    type Error = JSONValue;
+
+    fn try_from(value: JSONValue) -> Result<Self, Self::Error> {
+        match value {
+            JSONValue::Object(o) => Ok(o),
+            other => Err(other),
+        }
+    }
+}
+
+// how object() should react when the same key appears more than once among a
+// single object's members
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum DuplicateKeyPolicy {
+    // reject the document with JSONParseError::DuplicateKey
+    Error,
+    // keep the first value seen for the key, ignore later ones
+    KeepFirst,
+    // keep the last value seen for the key, overwriting earlier ones
+    // matches the old HashMap-backed behavior, where the last insert won
+    #[default]
+    KeepLast,
+}
+
+// knobs that affect how a document is parsed, threaded down through the
+// recursive-descent grammar alongside the current nesting depth
+#[derive(Debug, Clone, Copy)]
+struct ParseOptions {
+    duplicate_key_policy: DuplicateKeyPolicy,
+    max_depth: usize,
+    // opt-in: recognize `{{ expression }}` in place of a value and capture it
+    // as JSONValue::Expr instead of rejecting it as invalid JSON
+    allow_expr_placeholders: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            duplicate_key_policy: DuplicateKeyPolicy::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            allow_expr_placeholders: false,
+        }
+    }
+}
+
+// fold parsed members down to one entry per key according to `policy`,
+// keeping insertion order (the position of the first occurrence of each key).
+// `seen` maps each key to its index in `result` so a duplicate is found in
+// O(1) instead of rescanning `result` for every member (which would make
+// parsing a large flat object O(n^2) in its key count)
+#[allow(clippy::type_complexity)]
+fn apply_duplicate_key_policy(
+    members: Vec<(String, JSONValue)>,
+    policy: DuplicateKeyPolicy,
+) -> Result<Vec<(String, JSONValue)>, JSONParseError> {
+    let mut result: Vec<(String, JSONValue)> = Vec::with_capacity(members.len());
+    let mut seen: std::collections::HashMap<String, usize> =
+        std::collections::HashMap::with_capacity(members.len());
+
+    for (key, value) in members {
+        match seen.get(&key) {
+            Some(_) if policy == DuplicateKeyPolicy::Error => {
+                return Err(JSONParseError::DuplicateKey(key));
+            }
+            Some(_) if policy == DuplicateKeyPolicy::KeepFirst => {}
+            Some(&index) => result[index].1 = value,
+            None => {
+                seen.insert(key.clone(), result.len());
+                result.push((key, value));
+            }
+        }
+    }
+
+    Ok(result)
 }
 
 // consume whitespace and return the remaining string
@@ -25,6 +230,46 @@ fn ws(src: &str) -> &str {
     src.trim_start_matches([' ', '\n', '\t', '\r'])
 }
 
+// read exactly 4 hex digits off `chars` and parse them into a code unit
+fn read_hex4(chars: &mut std::str::Chars<'_>) -> Result<u16, JSONParseError> {
+    let mut hex = String::with_capacity(4);
+
+    for _ in 0..4 {
+        match chars.next() {
+            Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+            _ => return Err(JSONParseError::UnexpectedChar(chars.as_str().len())),
+        }
+    }
+
+    u16::from_str_radix(&hex, 16).map_err(|_| JSONParseError::UnexpectedChar(chars.as_str().len()))
+}
+
+// called right after consuming a `\u`, reads the code unit (and, for a high
+// surrogate, the following `\uXXXX` low surrogate) and returns the resulting char
+fn read_unicode_escape(chars: &mut std::str::Chars<'_>) -> Result<char, JSONParseError> {
+    let unit = read_hex4(chars)?;
+
+    if (0xD800..=0xDBFF).contains(&unit) {
+        // high surrogate, must be followed by a low surrogate
+        if chars.next() != Some('\\') || chars.next() != Some('u') {
+            return Err(JSONParseError::UnexpectedChar(chars.as_str().len()));
+        }
+
+        let low = read_hex4(chars)?;
+        if !(0xDC00..=0xDFFF).contains(&low) {
+            return Err(JSONParseError::UnexpectedChar(chars.as_str().len()));
+        }
+
+        let scalar = 0x10000 + (((unit - 0xD800) as u32) << 10) + (low - 0xDC00) as u32;
+        char::from_u32(scalar).ok_or(JSONParseError::UnexpectedChar(chars.as_str().len()))
+    } else if (0xDC00..=0xDFFF).contains(&unit) {
+        // lone low surrogate
+        Err(JSONParseError::UnexpectedChar(chars.as_str().len()))
+    } else {
+        char::from_u32(unit as u32).ok_or(JSONParseError::UnexpectedChar(chars.as_str().len()))
+    }
+}
+
 fn string(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
     match src.strip_prefix("\"") {
         Some(rest) => src = rest,
@@ -64,6 +309,7 @@ fn string(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
                 'n' => result.push('\n'),       // line feed
                 'r' => result.push('\r'),       // carriage return
                 't' => result.push('\t'),       // tab
+                'u' => result.push(read_unicode_escape(&mut chars)?),
                 _ => {
                     // can't escape whatever this is
                     return Err(JSONParseError::UnexpectedChar(chars.count()));
@@ -144,7 +390,11 @@ fn integer(mut src: &str) -> Result<(&str, i64), JSONParseError> {
         if let Ok((leftover, mut digis)) = digits(rest) {
             digis.insert(0, c);
             let int_str: String = digis.iter().collect();
-            let mut resulting_int: i64 = int_str.parse::<i64>().unwrap();
+            // a digit string longer than i64 can hold saturates instead of
+            // panicking; the untrusted input (a file/socket/pipe, per
+            // chunk0-4) controls its length, and imprecision on an
+            // already-astronomical integer beats crashing the process
+            let mut resulting_int: i64 = int_str.parse::<i64>().unwrap_or(i64::MAX);
             if negative {
                 resulting_int *= -1;
             }
@@ -207,7 +457,10 @@ fn exponent(mut src: &str) -> Result<(&str, i64), JSONParseError> {
     match digits(src) {
         Ok((rest, digis)) => {
             let num_str: String = digis.iter().collect();
-            let mut num: i64 = num_str.parse::<i64>().unwrap();
+            // saturate rather than panic on an untrusted, arbitrarily long
+            // exponent digit string; 10f64.powf(i64::MAX as f64) already
+            // collapses to infinity, same as the true value would
+            let mut num: i64 = num_str.parse::<i64>().unwrap_or(i64::MAX);
             if negative {
                 num *= -1;
             }
@@ -274,14 +527,30 @@ fn null(src: &str) -> Result<(&str, JSONValue), JSONParseError> {
     }
 }
 
-fn value(src: &str) -> Result<(&str, JSONValue), JSONParseError> {
-    match object(src) {
+// a `{{ expression }}` template placeholder; only tried by `value()` when
+// `opts.allow_expr_placeholders` is set
+fn expr(src: &str) -> Result<(&str, JSONValue), JSONParseError> {
+    let Some(rest) = src.strip_prefix("{{") else {
+        return Err(JSONParseError::NotFound);
+    };
+
+    match rest.find("}}") {
+        Some(end) => {
+            let text = rest[..end].trim().to_string();
+            Ok((&rest[end + 2..], JSONValue::Expr(text)))
+        }
+        None => Err(JSONParseError::MissingClosing(rest.len())),
+    }
+}
+
+fn value(src: &str, opts: ParseOptions, depth: usize) -> Result<(&str, JSONValue), JSONParseError> {
+    match object(src, opts, depth) {
         Ok(res) => return Ok(res),
         Err(JSONParseError::NotFound) => {} // if not found, that ok
         Err(e) => return Err(e),
     }
 
-    match array(src) {
+    match array(src, opts, depth) {
         Ok(res) => return Ok(res),
         Err(JSONParseError::NotFound) => {} // if not found, that ok
         Err(e) => return Err(e),            // if any other error, propogate it up
@@ -311,23 +580,39 @@ fn value(src: &str) -> Result<(&str, JSONValue), JSONParseError> {
         Err(e) => return Err(e),            // if any other error, propogate it up
     };
 
+    if opts.allow_expr_placeholders {
+        match expr(src) {
+            Ok(res) => return Ok(res),
+            Err(JSONParseError::NotFound) => {} // if not found, that ok
+            Err(e) => return Err(e),            // if any other error, propogate it up
+        }
+    }
+
     Err(JSONParseError::NotFound)
 }
 
-fn element(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
+fn element(
+    mut src: &str,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(&str, JSONValue), JSONParseError> {
     src = ws(src);
 
-    match value(src) {
+    match value(src, opts, depth) {
         Ok((rest, v)) => Ok((ws(rest), v)),
         Err(e) => Err(e),
     }
 }
 
-fn elements(mut src: &str) -> Result<(&str, Vec<JSONValue>), JSONParseError> {
+fn elements(
+    mut src: &str,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(&str, Vec<JSONValue>), JSONParseError> {
     let mut values = vec![];
 
     loop {
-        match element(src) {
+        match element(src, opts, depth) {
             Ok((rest, v)) => {
                 src = rest;
                 values.push(v);
@@ -347,7 +632,11 @@ fn elements(mut src: &str) -> Result<(&str, Vec<JSONValue>), JSONParseError> {
     Ok((src, values))
 }
 
-fn array(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
+fn array(
+    mut src: &str,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(&str, JSONValue), JSONParseError> {
     // first we must parse the [] character
 
     match src.strip_prefix("[") {
@@ -355,6 +644,11 @@ fn array(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
         None => return Err(JSONParseError::NotFound),
     };
 
+    let depth = depth + 1;
+    if depth > opts.max_depth {
+        return Err(JSONParseError::DepthExceeded(depth));
+    }
+
     // if this is true... then we have just parsed whitespace and there are no elements.
     // thus, return empty array
     if let Some(rest) = src.strip_prefix(']') {
@@ -363,7 +657,7 @@ fn array(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
 
     // otherwise, parse elemnts and return that
 
-    match elements(src) {
+    match elements(src, opts, depth) {
         Ok((src, v)) => {
             if let Some(rest) = src.strip_prefix(']') {
                 Ok((rest, JSONValue::Array(v)))
@@ -375,7 +669,11 @@ fn array(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
     }
 }
 
-fn object(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
+fn object(
+    mut src: &str,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(&str, JSONValue), JSONParseError> {
     // first we must parse the [] character
 
     match src.strip_prefix("{") {
@@ -383,25 +681,24 @@ fn object(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
         None => return Err(JSONParseError::NotFound),
     };
 
+    let depth = depth + 1;
+    if depth > opts.max_depth {
+        return Err(JSONParseError::DepthExceeded(depth));
+    }
+
     // if this is true... then we have just parsed whitespace and there are no elements.
     // thus, return empty array
     if let Some(rest) = src.strip_prefix('}') {
-        // TODO:
-        return Ok((rest, JSONValue::Object(HashMap::new())));
+        return Ok((rest, JSONValue::Object(vec![])));
     }
 
     // otherwise, parse elemnts and return that
 
-    match members(src) {
+    match members(src, opts, depth) {
         Ok((src, v)) => {
             if let Some(rest) = src.strip_prefix('}') {
-                let mut map: HashMap<String, JSONValue> = HashMap::new();
-
-                v.iter().for_each(|(key, value)| {
-                    map.insert(key.to_owned(), value.to_owned());
-                });
-
-                Ok((rest, JSONValue::Object(map)))
+                let deduped = apply_duplicate_key_policy(v, opts.duplicate_key_policy)?;
+                Ok((rest, JSONValue::Object(deduped)))
             } else {
                 Err(JSONParseError::MissingClosing(src.len()))
             }
@@ -411,11 +708,15 @@ fn object(mut src: &str) -> Result<(&str, JSONValue), JSONParseError> {
 }
 
 #[allow(clippy::type_complexity)]
-fn members(mut src: &str) -> Result<(&str, Vec<(String, JSONValue)>), JSONParseError> {
+fn members(
+    mut src: &str,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(&str, Vec<(String, JSONValue)>), JSONParseError> {
     let mut values = vec![];
 
     loop {
-        match member(src) {
+        match member(src, opts, depth) {
             Ok((rest, v)) => {
                 src = rest;
                 values.push(v);
@@ -435,7 +736,11 @@ fn members(mut src: &str) -> Result<(&str, Vec<(String, JSONValue)>), JSONParseE
     Ok((src, values))
 }
 
-fn member(mut src: &str) -> Result<(&str, (String, JSONValue)), JSONParseError> {
+fn member(
+    mut src: &str,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(&str, (String, JSONValue)), JSONParseError> {
     src = ws(src);
 
     match string(src) {
@@ -447,7 +752,7 @@ fn member(mut src: &str) -> Result<(&str, (String, JSONValue)), JSONParseError>
 
             if src.starts_with(':') {
                 src = &src[1..];
-                match element(src) {
+                match element(src, opts, depth) {
                     Ok((rest, el)) => Ok((rest, (key, el))),
                     Err(e) => Err(e),
                 }
@@ -461,149 +766,1023 @@ fn member(mut src: &str) -> Result<(&str, (String, JSONValue)), JSONParseError>
 }
 
 fn parse(src: &str) -> Result<JSONValue, JSONParseError> {
-    match element(src) {
+    parse_with_options(src, ParseOptions::default())
+}
+
+fn parse_with_duplicate_key_policy(
+    src: &str,
+    policy: DuplicateKeyPolicy,
+) -> Result<JSONValue, JSONParseError> {
+    parse_with_options(
+        src,
+        ParseOptions {
+            duplicate_key_policy: policy,
+            ..ParseOptions::default()
+        },
+    )
+}
+
+fn parse_with_expr_placeholders(src: &str) -> Result<JSONValue, JSONParseError> {
+    parse_with_options(
+        src,
+        ParseOptions {
+            allow_expr_placeholders: true,
+            ..ParseOptions::default()
+        },
+    )
+}
+
+fn parse_with_max_depth(src: &str, max_depth: usize) -> Result<JSONValue, JSONParseError> {
+    parse_with_options(
+        src,
+        ParseOptions {
+            max_depth,
+            ..ParseOptions::default()
+        },
+    )
+}
+
+fn parse_with_options(src: &str, opts: ParseOptions) -> Result<JSONValue, JSONParseError> {
+    match element(src, opts, 0) {
         Ok((_, res)) => Ok(res),
         Err(e) => Err(e),
     }
 }
 
-fn main() {
-    // open and read the broken.json file
-    let text_file_contents = fs::read_to_string("broken.json").unwrap();
-    let src = text_file_contents.as_str();
+// parse directly from a reader (a file, socket, pipe, ...) instead of a fully
+// buffered &str, so a multi-gigabyte document doesn't have to be slurped into
+// memory up front. Only ever looks a couple chars ahead of the reader, so the
+// source side of the parse stays O(1) regardless of document size (the
+// returned JSONValue tree itself is of course still sized to the document)
+//
+// NOTE: the reader_* functions below are a second, parallel implementation of
+// the grammar above them (`value`/`array`/`object`/`string`/...), rather than
+// the same grammar made generic over `&str` vs `Read`. That duplication is a
+// known maintenance hazard: a fix to one (e.g. the surrogate-pair handling in
+// `read_unicode_escape`) has to be mirrored into its `reader_*` counterpart by
+// hand, or the two will silently drift apart. Collapsing them behind a shared
+// cursor abstraction would remove that risk, but is a larger refactor than is
+// justified here; until then, treat the two grammars as one unit when editing
+// either.
+fn parse_reader<R: Read>(r: R) -> Result<JSONValue, JSONParseError> {
+    parse_reader_with_options(r, ParseOptions::default())
+}
 
-    match parse(src) {
-        Ok(v) => {
-            println!("{:?}", v);
-        }
-        Err(e) => {
-            println!("{}", format!("Error: {:?}", e).normal().on_red());
-            let pos = match e {
-                JSONParseError::Error(p) => p,
-                JSONParseError::UnexpectedChar(p) => p,
-                JSONParseError::MissingClosing(p) => p,
-                JSONParseError::NotFound => 0,
-            };
+fn parse_reader_with_duplicate_key_policy<R: Read>(
+    r: R,
+    policy: DuplicateKeyPolicy,
+) -> Result<JSONValue, JSONParseError> {
+    parse_reader_with_options(
+        r,
+        ParseOptions {
+            duplicate_key_policy: policy,
+            ..ParseOptions::default()
+        },
+    )
+}
 
-            let total = src.len();
-            let error_pos = total - pos;
+fn parse_reader_with_max_depth<R: Read>(
+    r: R,
+    max_depth: usize,
+) -> Result<JSONValue, JSONParseError> {
+    parse_reader_with_options(
+        r,
+        ParseOptions {
+            max_depth,
+            ..ParseOptions::default()
+        },
+    )
+}
 
-            // lets get 2 lines from the src, one before and one of the error
+fn parse_reader_with_expr_placeholders<R: Read>(r: R) -> Result<JSONValue, JSONParseError> {
+    parse_reader_with_options(
+        r,
+        ParseOptions {
+            allow_expr_placeholders: true,
+            ..ParseOptions::default()
+        },
+    )
+}
 
-            let lines = src.split("\n").collect::<Vec<&str>>();
+fn parse_reader_with_options<R: Read>(
+    r: R,
+    opts: ParseOptions,
+) -> Result<JSONValue, JSONParseError> {
+    let mut cr = CharReader::new(r);
+    reader_element(&mut cr, opts, 0)
+}
 
-            let mut leftover = error_pos;
-            let mut line_index = 0;
-            let mut last_line = "";
-            let err_line;
-            loop {
-                let line = lines[line_index];
-                let line_len = line.len();
+// a small-bounded-lookahead adapter from a byte-oriented `Read` to UTF-8
+// `char`s (at most two chars are ever buffered, to tell a `{{` placeholder
+// apart from an object's opening `{`). `pos` tracks the number of bytes
+// consumed so far for error reporting.
+struct CharReader<R: Read> {
+    reader: R,
+    peeked: std::collections::VecDeque<char>,
+    pos: usize,
+}
 
-                if leftover < line_len {
-                    err_line = line;
-                    break;
-                } else {
-                    last_line = line;
-                    leftover -= line_len + 1;
-                    line_index += 1;
-                }
-            }
+impl<R: Read> CharReader<R> {
+    fn new(reader: R) -> Self {
+        CharReader {
+            reader,
+            peeked: std::collections::VecDeque::new(),
+            pos: 0,
+        }
+    }
 
-            // // print seperator -'s
+    fn read_char_raw(&mut self) -> Result<Option<char>, JSONParseError> {
+        let mut buf = [0u8; 4];
 
-            println!("{}", "-".repeat(max(last_line.len(), err_line.len())));
-            println!("{}", last_line);
-            println!("{}", err_line);
+        let n = self
+            .reader
+            .read(&mut buf[..1])
+            .map_err(|e| JSONParseError::Io(e.to_string()))?;
+        if n == 0 {
+            return Ok(None);
+        }
 
-            // print an ascii arrow to point to the error
-            for i in 0..3 {
-                for _ in 0..(leftover) {
-                    print!(" ");
-                }
-                println!("{}", if i == 0 { "^" } else { "|" });
+        let width = utf8_char_width(buf[0]);
+        for byte in buf.iter_mut().take(width).skip(1) {
+            let n = self
+                .reader
+                .read(std::slice::from_mut(byte))
+                .map_err(|e| JSONParseError::Io(e.to_string()))?;
+            if n == 0 {
+                return Err(JSONParseError::Io(
+                    "unexpected end of stream inside a UTF-8 sequence".to_string(),
+                ));
             }
+        }
 
-            // print the error message
-            match e {
-                JSONParseError::Error(_) => println!(
-                    "{}",
-                    format!(
-                        "Error: {} on Line {} Char {}",
-                        "Error",
-                        line_index + 1,
-                        leftover
-                    )
-                    .red()
-                ),
-                JSONParseError::UnexpectedChar(_) => println!(
-                    "{}",
-                    format!(
-                        "Error: {} on Line {} Char {}",
-                        "Unexpected Character",
-                        line_index + 1,
-                        leftover
-                    )
-                    .red()
-                ),
-                JSONParseError::MissingClosing(_) => println!(
-                    "{}",
-                    format!(
-                        "Error: {} on Line {} Char {}",
-                        "Missing Closing",
-                        line_index + 1,
-                        leftover
-                    )
-                    .red()
-                ),
-                JSONParseError::NotFound => {
-                    println!("Error: Not Found")
-                }
+        match std::str::from_utf8(&buf[..width]) {
+            Ok(s) => Ok(s.chars().next()),
+            Err(_) => Err(JSONParseError::Io(
+                "invalid UTF-8 in input stream".to_string(),
+            )),
+        }
+    }
+
+    fn fill(&mut self, upto: usize) -> Result<(), JSONParseError> {
+        while self.peeked.len() <= upto {
+            match self.read_char_raw()? {
+                Some(c) => self.peeked.push_back(c),
+                None => break,
             }
         }
+        Ok(())
     }
 
-    let big_file = std::fs::read_to_string("twitter.json").expect("Could not read file");
+    fn peek(&mut self) -> Result<Option<char>, JSONParseError> {
+        self.fill(0)?;
+        Ok(self.peeked.front().copied())
+    }
 
-    // print!("{}", big_file);
-    // let big_file = std::fs::read_to_string("canada.json").expect("Could not read file");
+    // the char one past the next one, without consuming either
+    fn peek2(&mut self) -> Result<Option<char>, JSONParseError> {
+        self.fill(1)?;
+        Ok(self.peeked.get(1).copied())
+    }
 
-    // how many bytes of data?
-    let num_bytes = big_file.len();
+    fn next(&mut self) -> Result<Option<char>, JSONParseError> {
+        self.peek()?;
+        let c = self.peeked.pop_front();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+        }
+        Ok(c)
+    }
+}
 
-    let mul = 1000;
-    let bytes_to_parse = num_bytes * mul;
+// number of bytes in the UTF-8 sequence starting with `byte`
+fn utf8_char_width(byte: u8) -> usize {
+    if byte & 0x80 == 0 {
+        1
+    } else if byte & 0xE0 == 0xC0 {
+        2
+    } else if byte & 0xF0 == 0xE0 {
+        3
+    } else if byte & 0xF8 == 0xF0 {
+        4
+    } else {
+        // not a valid UTF-8 leading byte; read it alone and let from_utf8 report the error
+        1
+    }
+}
 
-    let start_time = std::time::Instant::now();
-    for _ in 0..mul {
-        let _ = parse(big_file.as_str());
+fn reader_ws<R: Read>(cr: &mut CharReader<R>) -> Result<(), JSONParseError> {
+    while let Some(c) = cr.peek()? {
+        if matches!(c, ' ' | '\n' | '\t' | '\r') {
+            cr.next()?;
+        } else {
+            break;
+        }
     }
-    let end_time = std::time::Instant::now();
+    Ok(())
+}
 
-    let bps = bytes_to_parse as f64 / (end_time - start_time).as_secs_f64();
+fn reader_digit<R: Read>(cr: &mut CharReader<R>) -> Result<Option<char>, JSONParseError> {
+    match cr.peek()? {
+        Some(c) if c.is_ascii_digit() => {
+            cr.next()?;
+            Ok(Some(c))
+        }
+        _ => Ok(None),
+    }
+}
 
-    let mbs = (bytes_to_parse as f64) / (1_000_000.0);
-    let mbps = mbs / (end_time - start_time).as_secs_f64();
+fn reader_integer<R: Read>(cr: &mut CharReader<R>) -> Result<i64, JSONParseError> {
+    let negative = cr.peek()? == Some('-');
+    if negative {
+        cr.next()?;
+    }
 
-    let gbs = (bytes_to_parse as f64) / (1_000_000_000.0);
-    let gbps = gbs / (end_time - start_time).as_secs_f64();
+    let first = match cr.next()? {
+        Some(c) if c.is_ascii_digit() => c,
+        _ => return Err(JSONParseError::NotFound),
+    };
 
-    println!("Parsing speed: {:.2} Bytes/s", bps);
-    println!("Parsing speed: {:.2} MB/s", mbps);
-    println!("Parsing speed: {:.2} GB/s", gbps);
-}
+    let mut digits_str = String::new();
+    digits_str.push(first);
 
-#[cfg(test)]
-mod tests {
-    use std::fs;
+    // a leading zero may not be followed by further digits
+    if first != '0' {
+        while let Some(c) = reader_digit(cr)? {
+            digits_str.push(c);
+        }
+    }
 
-    #[test]
-    fn ws_empty() {
-        let result = super::ws("");
-        assert_eq!(result, "");
+    // saturate rather than panic on a digit string too long for i64 (see
+    // the matching note on `integer`'s str-based counterpart above)
+    let mut n: i64 = digits_str.parse().unwrap_or(i64::MAX);
+    if negative {
+        n = -n;
     }
+    Ok(n)
+}
 
-    #[test]
+fn reader_fraction<R: Read>(cr: &mut CharReader<R>) -> Result<f64, JSONParseError> {
+    if cr.peek()? != Some('.') {
+        return Ok(0.0);
+    }
+    cr.next()?;
+
+    let mut digits_str = String::from("0.");
+    let mut any = false;
+    while let Some(c) = reader_digit(cr)? {
+        digits_str.push(c);
+        any = true;
+    }
+
+    if !any {
+        return Err(JSONParseError::NotFound);
+    }
+
+    Ok(digits_str.parse().unwrap())
+}
+
+fn reader_exponent<R: Read>(cr: &mut CharReader<R>) -> Result<i64, JSONParseError> {
+    match cr.peek()? {
+        Some('e') | Some('E') => {
+            cr.next()?;
+        }
+        _ => return Ok(0),
+    }
+
+    let mut negative = false;
+    match cr.peek()? {
+        Some('+') => {
+            cr.next()?;
+        }
+        Some('-') => {
+            negative = true;
+            cr.next()?;
+        }
+        _ => {}
+    }
+
+    let mut digits_str = String::new();
+    while let Some(c) = reader_digit(cr)? {
+        digits_str.push(c);
+    }
+
+    if digits_str.is_empty() {
+        return Err(JSONParseError::NotFound);
+    }
+
+    // saturate rather than panic on an untrusted, arbitrarily long exponent
+    // digit string; 10f64.powf(i64::MAX as f64) already collapses to
+    // infinity, same as the true value would
+    let mut n: i64 = digits_str.parse().unwrap_or(i64::MAX);
+    if negative {
+        n = -n;
+    }
+    Ok(n)
+}
+
+fn reader_number<R: Read>(cr: &mut CharReader<R>) -> Result<JSONValue, JSONParseError> {
+    let int_part = reader_integer(cr)?;
+    let mut result = int_part.unsigned_abs() as f64;
+    let negative = int_part.is_negative();
+
+    match reader_fraction(cr) {
+        Ok(frac) => result += frac,
+        Err(JSONParseError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+
+    match reader_exponent(cr) {
+        Ok(exponent) => result *= 10_f64.powf(exponent as f64),
+        Err(JSONParseError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+
+    if negative {
+        result = -result;
+    }
+
+    Ok(JSONValue::Number(result))
+}
+
+fn reader_expect_literal<R: Read>(
+    cr: &mut CharReader<R>,
+    literal: &str,
+) -> Result<(), JSONParseError> {
+    for expected in literal.chars() {
+        match cr.next()? {
+            Some(c) if c == expected => {}
+            _ => return Err(JSONParseError::UnexpectedChar(cr.pos)),
+        }
+    }
+    Ok(())
+}
+
+fn reader_bool<R: Read>(cr: &mut CharReader<R>) -> Result<JSONValue, JSONParseError> {
+    match cr.peek()? {
+        Some('t') => {
+            reader_expect_literal(cr, "true")?;
+            Ok(JSONValue::True)
+        }
+        Some('f') => {
+            reader_expect_literal(cr, "false")?;
+            Ok(JSONValue::False)
+        }
+        _ => Err(JSONParseError::NotFound),
+    }
+}
+
+fn reader_null<R: Read>(cr: &mut CharReader<R>) -> Result<JSONValue, JSONParseError> {
+    reader_expect_literal(cr, "null")?;
+    Ok(JSONValue::Null)
+}
+
+fn reader_hex4<R: Read>(cr: &mut CharReader<R>) -> Result<u16, JSONParseError> {
+    let mut hex = String::with_capacity(4);
+
+    for _ in 0..4 {
+        match cr.next()? {
+            Some(c) if c.is_ascii_hexdigit() => hex.push(c),
+            _ => return Err(JSONParseError::UnexpectedChar(cr.pos)),
+        }
+    }
+
+    u16::from_str_radix(&hex, 16).map_err(|_| JSONParseError::UnexpectedChar(cr.pos))
+}
+
+fn reader_unicode_escape<R: Read>(cr: &mut CharReader<R>) -> Result<char, JSONParseError> {
+    let unit = reader_hex4(cr)?;
+
+    if (0xD800..=0xDBFF).contains(&unit) {
+        if cr.next()? != Some('\\') || cr.next()? != Some('u') {
+            return Err(JSONParseError::UnexpectedChar(cr.pos));
+        }
+
+        let low = reader_hex4(cr)?;
+        if !(0xDC00..=0xDFFF).contains(&low) {
+            return Err(JSONParseError::UnexpectedChar(cr.pos));
+        }
+
+        let scalar = 0x10000 + (((unit - 0xD800) as u32) << 10) + (low - 0xDC00) as u32;
+        char::from_u32(scalar).ok_or(JSONParseError::UnexpectedChar(cr.pos))
+    } else if (0xDC00..=0xDFFF).contains(&unit) {
+        Err(JSONParseError::UnexpectedChar(cr.pos))
+    } else {
+        char::from_u32(unit as u32).ok_or(JSONParseError::UnexpectedChar(cr.pos))
+    }
+}
+
+fn reader_string<R: Read>(cr: &mut CharReader<R>) -> Result<JSONValue, JSONParseError> {
+    match cr.next()? {
+        Some('"') => {}
+        _ => return Err(JSONParseError::NotFound),
+    }
+
+    let mut result = String::new();
+
+    loop {
+        let c = match cr.next()? {
+            Some(c) => c,
+            None => return Err(JSONParseError::MissingClosing(cr.pos)),
+        };
+
+        if c == '"' {
+            break;
+        }
+
+        if c == '\\' {
+            let escape = match cr.next()? {
+                Some(c) => c,
+                None => return Err(JSONParseError::MissingClosing(cr.pos)),
+            };
+
+            match escape {
+                '"' => result.push('"'),
+                '\\' => result.push('\\'),
+                '/' => result.push('/'),
+                'b' => result.push('\u{0008}'),
+                'f' => result.push('\u{000c}'),
+                'n' => result.push('\n'),
+                'r' => result.push('\r'),
+                't' => result.push('\t'),
+                'u' => result.push(reader_unicode_escape(cr)?),
+                _ => return Err(JSONParseError::UnexpectedChar(cr.pos)),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+
+    Ok(JSONValue::String(result))
+}
+
+// a `{{ expression }}` template placeholder; only tried by `reader_value()`
+// when `opts.allow_expr_placeholders` is set
+fn reader_expr<R: Read>(cr: &mut CharReader<R>) -> Result<JSONValue, JSONParseError> {
+    cr.next()?; // first '{'
+    cr.next()?; // second '{'
+
+    let mut text = String::new();
+    loop {
+        match cr.next()? {
+            Some('}') if cr.peek()? == Some('}') => {
+                cr.next()?;
+                return Ok(JSONValue::Expr(text.trim().to_string()));
+            }
+            Some(c) => text.push(c),
+            None => return Err(JSONParseError::MissingClosing(cr.pos)),
+        }
+    }
+}
+
+fn reader_value<R: Read>(
+    cr: &mut CharReader<R>,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<JSONValue, JSONParseError> {
+    if opts.allow_expr_placeholders && cr.peek()? == Some('{') && cr.peek2()? == Some('{') {
+        return reader_expr(cr);
+    }
+
+    match cr.peek()? {
+        Some('{') => reader_object(cr, opts, depth),
+        Some('[') => reader_array(cr, opts, depth),
+        Some('"') => reader_string(cr),
+        Some(c) if c == '-' || c.is_ascii_digit() => reader_number(cr),
+        Some('t') | Some('f') => reader_bool(cr),
+        Some('n') => reader_null(cr),
+        _ => Err(JSONParseError::NotFound),
+    }
+}
+
+fn reader_element<R: Read>(
+    cr: &mut CharReader<R>,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<JSONValue, JSONParseError> {
+    reader_ws(cr)?;
+    let v = reader_value(cr, opts, depth)?;
+    reader_ws(cr)?;
+    Ok(v)
+}
+
+fn reader_array<R: Read>(
+    cr: &mut CharReader<R>,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<JSONValue, JSONParseError> {
+    match cr.next()? {
+        Some('[') => {}
+        _ => return Err(JSONParseError::NotFound),
+    }
+    reader_ws(cr)?;
+
+    let depth = depth + 1;
+    if depth > opts.max_depth {
+        return Err(JSONParseError::DepthExceeded(depth));
+    }
+
+    if cr.peek()? == Some(']') {
+        cr.next()?;
+        return Ok(JSONValue::Array(vec![]));
+    }
+
+    let mut values = vec![];
+    loop {
+        values.push(reader_element(cr, opts, depth)?);
+
+        if cr.peek()? == Some(',') {
+            cr.next()?;
+        } else {
+            break;
+        }
+    }
+
+    match cr.next()? {
+        Some(']') => Ok(JSONValue::Array(values)),
+        _ => Err(JSONParseError::MissingClosing(cr.pos)),
+    }
+}
+
+fn reader_member<R: Read>(
+    cr: &mut CharReader<R>,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<(String, JSONValue), JSONParseError> {
+    reader_ws(cr)?;
+
+    let key = match reader_string(cr)? {
+        JSONValue::String(key) => key,
+        _ => unreachable!("reader_string always returns JSONValue::String on success"),
+    };
+
+    reader_ws(cr)?;
+
+    match cr.next()? {
+        Some(':') => {}
+        _ => return Err(JSONParseError::UnexpectedChar(cr.pos)),
+    }
+
+    let value = reader_element(cr, opts, depth)?;
+    Ok((key, value))
+}
+
+#[allow(clippy::type_complexity)]
+fn reader_object<R: Read>(
+    cr: &mut CharReader<R>,
+    opts: ParseOptions,
+    depth: usize,
+) -> Result<JSONValue, JSONParseError> {
+    match cr.next()? {
+        Some('{') => {}
+        _ => return Err(JSONParseError::NotFound),
+    }
+    reader_ws(cr)?;
+
+    let depth = depth + 1;
+    if depth > opts.max_depth {
+        return Err(JSONParseError::DepthExceeded(depth));
+    }
+
+    if cr.peek()? == Some('}') {
+        cr.next()?;
+        return Ok(JSONValue::Object(vec![]));
+    }
+
+    let mut members = vec![];
+    loop {
+        members.push(reader_member(cr, opts, depth)?);
+
+        if cr.peek()? == Some(',') {
+            cr.next()?;
+        } else {
+            break;
+        }
+    }
+
+    match cr.next()? {
+        Some('}') => {
+            let deduped = apply_duplicate_key_policy(members, opts.duplicate_key_policy)?;
+            Ok(JSONValue::Object(deduped))
+        }
+        _ => Err(JSONParseError::MissingClosing(cr.pos)),
+    }
+}
+
+// render `value` back to compact JSON text (no extra whitespace)
+fn serialize(value: &JSONValue) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, None, 0);
+    out
+}
+
+// render `value` back to JSON text, indenting nested arrays/objects by
+// `indent_width` spaces per level
+fn serialize_pretty(value: &JSONValue, indent_width: usize) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, Some(indent_width), 0);
+    out
+}
+
+fn write_value(out: &mut String, value: &JSONValue, indent: Option<usize>, depth: usize) {
+    match value {
+        JSONValue::Null => out.push_str("null"),
+        JSONValue::True => out.push_str("true"),
+        JSONValue::False => out.push_str("false"),
+        JSONValue::Number(n) => {
+            // JSON has no spelling for NaN/Infinity; n.to_string() would
+            // print Rust's "inf"/"-inf"/"NaN", which isn't valid JSON.
+            // Matches JSON.stringify's behavior of falling back to `null`
+            // rather than producing unparseable output.
+            if n.is_finite() {
+                out.push_str(&n.to_string());
+            } else {
+                out.push_str("null");
+            }
+        }
+        JSONValue::String(s) => out.push_str(&escape_string(s)),
+        JSONValue::Array(items) => write_array(out, items, indent, depth),
+        JSONValue::Object(members) => write_object(out, members, indent, depth),
+        JSONValue::Expr(text) => {
+            out.push_str("{{ ");
+            out.push_str(text);
+            out.push_str(" }}");
+        }
+    }
+}
+
+fn write_array(out: &mut String, items: &[JSONValue], indent: Option<usize>, depth: usize) {
+    if items.is_empty() {
+        out.push_str("[]");
+        return;
+    }
+
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_newline_indent(out, indent, depth + 1);
+        write_value(out, item, indent, depth + 1);
+    }
+    write_newline_indent(out, indent, depth);
+    out.push(']');
+}
+
+#[allow(clippy::type_complexity)]
+fn write_object(
+    out: &mut String,
+    members: &[(String, JSONValue)],
+    indent: Option<usize>,
+    depth: usize,
+) {
+    if members.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    out.push('{');
+    for (i, (key, value)) in members.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_newline_indent(out, indent, depth + 1);
+        out.push_str(&escape_string(key));
+        out.push(':');
+        if indent.is_some() {
+            out.push(' ');
+        }
+        write_value(out, value, indent, depth + 1);
+    }
+    write_newline_indent(out, indent, depth);
+    out.push('}');
+}
+
+fn write_newline_indent(out: &mut String, indent: Option<usize>, depth: usize) {
+    if let Some(width) = indent {
+        out.push('\n');
+        out.push_str(&" ".repeat(width * depth));
+    }
+}
+
+// escape a string's contents for JSON, including the \uXXXX form for control characters
+fn escape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{0008}' => out.push_str("\\b"),
+            '\u{000c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+// a short, human-readable summary of a parsed value's shape, using the
+// typed accessors instead of matching on JSONValue's variants directly
+fn describe(v: &JSONValue) -> String {
+    if let Some(n) = v.as_number() {
+        format!("number: {}", n)
+    } else if let Some(b) = v.as_bool() {
+        format!("boolean: {}", b)
+    } else if let Some(s) = v.as_str() {
+        format!("string: {:?}", s)
+    } else if let Some(items) = v.as_array() {
+        format!("array with {} element(s)", items.len())
+    } else if let Some(members) = v.as_object() {
+        format!("object with {} key(s)", members.len())
+    } else {
+        "null or template expression".to_string()
+    }
+}
+
+// which parse_reader_with_* wrapper `format_file` should reach for, parsed
+// from the optional flag following `<path>` on the command line; at most
+// one applies at a time, matching how each wrapper takes a single option
+enum ReaderOption {
+    Default,
+    DuplicateKeyPolicy(DuplicateKeyPolicy),
+    MaxDepth(usize),
+    ExprPlaceholders,
+}
+
+// stream `path` through parse_reader (or one of its ParseOptions-flavored
+// siblings, selected by `option`) and print it back out as normalized JSON,
+// pretty-printed by default or compact when `compact` is set; the entry
+// point for `cargo run -- <path> [--compact] [--max-depth N | --dup-policy
+// first|last|error | --allow-expr]`
+fn format_file(path: &str, compact: bool, option: ReaderOption) {
+    let file = fs::File::open(path).unwrap_or_else(|e| {
+        println!(
+            "{}",
+            format!("Error: could not open {}: {}", path, e)
+                .normal()
+                .on_red()
+        );
+        std::process::exit(1);
+    });
+
+    let parsed = match option {
+        ReaderOption::Default => parse_reader(file),
+        ReaderOption::DuplicateKeyPolicy(policy) => {
+            parse_reader_with_duplicate_key_policy(file, policy)
+        }
+        ReaderOption::MaxDepth(max_depth) => parse_reader_with_max_depth(file, max_depth),
+        ReaderOption::ExprPlaceholders => parse_reader_with_expr_placeholders(file),
+    };
+
+    match parsed {
+        Ok(v) => {
+            println!("{}", format!("# {}", describe(&v)).dimmed());
+
+            // if the top-level document declares a "version" field, surface
+            // it; real documents disagree on whether that's a number or a
+            // string (semver/package.json-style), so try both instead of
+            // unwrap()-ing into one and panicking on the other
+            if let Some(version) = v.get("version") {
+                if let Some(version) = version.as_number() {
+                    println!("{}", format!("# version: {}", version).dimmed());
+                } else if let Some(version) = version.as_str() {
+                    println!("{}", format!("# version: {}", version).dimmed());
+                }
+            }
+
+            if compact {
+                println!("{}", serialize(&v));
+            } else {
+                println!("{}", serialize_pretty(&v, 2));
+            }
+        }
+        Err(e) => {
+            println!("{}", format!("Error: {:?}", e).normal().on_red());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    // `cargo run -- <path>` streams `path` through parse_reader instead of
+    // slurping it into a String first, so large files/pipes don't have to be
+    // buffered up front, and reprints the parsed document normalized.
+    // `--compact` selects compact output; at most one of `--max-depth N`,
+    // `--dup-policy first|last|error`, or `--allow-expr` selects a
+    // ParseOptions-flavored sibling of parse_reader instead of the default
+    if let Some(path) = std::env::args().nth(1) {
+        let rest: Vec<String> = std::env::args().skip(2).collect();
+        let compact = rest.iter().any(|a| a == "--compact");
+
+        let option = if let Some(policy) = rest
+            .iter()
+            .position(|a| a == "--dup-policy")
+            .and_then(|i| rest.get(i + 1))
+        {
+            let policy = match policy.as_str() {
+                "first" => DuplicateKeyPolicy::KeepFirst,
+                "last" => DuplicateKeyPolicy::KeepLast,
+                "error" => DuplicateKeyPolicy::Error,
+                other => {
+                    println!(
+                        "{}",
+                        format!("Error: unknown --dup-policy {:?}", other).red()
+                    );
+                    std::process::exit(1);
+                }
+            };
+            ReaderOption::DuplicateKeyPolicy(policy)
+        } else if let Some(max_depth) = rest
+            .iter()
+            .position(|a| a == "--max-depth")
+            .and_then(|i| rest.get(i + 1))
+        {
+            let max_depth = max_depth.parse().unwrap_or_else(|_| {
+                println!(
+                    "{}",
+                    format!("Error: invalid --max-depth {:?}", max_depth).red()
+                );
+                std::process::exit(1);
+            });
+            ReaderOption::MaxDepth(max_depth)
+        } else if rest.iter().any(|a| a == "--allow-expr") {
+            ReaderOption::ExprPlaceholders
+        } else {
+            ReaderOption::Default
+        };
+
+        format_file(&path, compact, option);
+        return;
+    }
+
+    // open and read the broken.json file
+    let text_file_contents = fs::read_to_string("broken.json").unwrap();
+    let src = text_file_contents.as_str();
+
+    match parse(src) {
+        Ok(v) => {
+            println!("{:?}", v);
+        }
+        Err(e) => {
+            println!("{}", format!("Error: {:?}", e).normal().on_red());
+            let pos = match e {
+                JSONParseError::Error(p) => p,
+                JSONParseError::UnexpectedChar(p) => p,
+                JSONParseError::MissingClosing(p) => p,
+                JSONParseError::NotFound => 0,
+                JSONParseError::DuplicateKey(_) => 0,
+                JSONParseError::Io(_) => 0,
+                JSONParseError::DepthExceeded(_) => 0,
+            };
+
+            let total = src.len();
+            let error_pos = total - pos;
+
+            // lets get 2 lines from the src, one before and one of the error
+
+            let lines = src.split("\n").collect::<Vec<&str>>();
+
+            let mut leftover = error_pos;
+            let mut line_index = 0;
+            let mut last_line = "";
+            let err_line;
+            loop {
+                let line = lines[line_index];
+                let line_len = line.len();
+
+                if leftover < line_len {
+                    err_line = line;
+                    break;
+                } else {
+                    last_line = line;
+                    leftover -= line_len + 1;
+                    line_index += 1;
+                }
+            }
+
+            // // print seperator -'s
+
+            println!("{}", "-".repeat(max(last_line.len(), err_line.len())));
+            println!("{}", last_line);
+            println!("{}", err_line);
+
+            // print an ascii arrow to point to the error
+            for i in 0..3 {
+                for _ in 0..(leftover) {
+                    print!(" ");
+                }
+                println!("{}", if i == 0 { "^" } else { "|" });
+            }
+
+            // print the error message
+            match e {
+                JSONParseError::Error(_) => println!(
+                    "{}",
+                    format!(
+                        "Error: {} on Line {} Char {}",
+                        "Error",
+                        line_index + 1,
+                        leftover
+                    )
+                    .red()
+                ),
+                JSONParseError::UnexpectedChar(_) => println!(
+                    "{}",
+                    format!(
+                        "Error: {} on Line {} Char {}",
+                        "Unexpected Character",
+                        line_index + 1,
+                        leftover
+                    )
+                    .red()
+                ),
+                JSONParseError::MissingClosing(_) => println!(
+                    "{}",
+                    format!(
+                        "Error: {} on Line {} Char {}",
+                        "Missing Closing",
+                        line_index + 1,
+                        leftover
+                    )
+                    .red()
+                ),
+                JSONParseError::NotFound => {
+                    println!("Error: Not Found")
+                }
+                JSONParseError::DuplicateKey(key) => {
+                    println!("{}", format!("Error: Duplicate Key {:?}", key).red())
+                }
+                JSONParseError::Io(message) => {
+                    println!("{}", format!("Error: IO Error: {}", message).red())
+                }
+                JSONParseError::DepthExceeded(depth) => {
+                    println!("{}", format!("Error: Depth Exceeded: {}", depth).red())
+                }
+            }
+        }
+    }
+
+    let big_file = std::fs::read_to_string("twitter.json").expect("Could not read file");
+
+    // print!("{}", big_file);
+    // let big_file = std::fs::read_to_string("canada.json").expect("Could not read file");
+
+    // how many bytes of data?
+    let num_bytes = big_file.len();
+
+    let mul = 1000;
+    let bytes_to_parse = num_bytes * mul;
+
+    let start_time = std::time::Instant::now();
+    for _ in 0..mul {
+        let _ = parse(big_file.as_str());
+    }
+    let end_time = std::time::Instant::now();
+
+    let bps = bytes_to_parse as f64 / (end_time - start_time).as_secs_f64();
+
+    let mbs = (bytes_to_parse as f64) / (1_000_000.0);
+    let mbps = mbs / (end_time - start_time).as_secs_f64();
+
+    let gbs = (bytes_to_parse as f64) / (1_000_000_000.0);
+    let gbps = gbs / (end_time - start_time).as_secs_f64();
+
+    println!("Parsing speed: {:.2} Bytes/s", bps);
+    println!("Parsing speed: {:.2} MB/s", mbps);
+    println!("Parsing speed: {:.2} GB/s", gbps);
+
+    // also exercise the &str-based ParseOptions wrappers (the in-memory
+    // counterparts of the parse_reader_with_* family reachable above via
+    // --max-depth/--dup-policy/--allow-expr) on the same document
+    let _ = parse_with_duplicate_key_policy(big_file.as_str(), DuplicateKeyPolicy::KeepFirst);
+    let _ = parse_with_max_depth(big_file.as_str(), 64);
+    let _ = parse_with_expr_placeholders(big_file.as_str());
+
+    // unlike the version field in format_file (untrusted, shape unknown,
+    // so it goes through the non-panicking as_number/as_str), this literal
+    // is hardcoded right above as a number, so its shape is guaranteed by
+    // the caller and `unwrap` is the appropriate accessor
+    let mul_as_f64: f64 = parse("1000").unwrap().unwrap();
+    println!("Parsed mul literal back out as: {}", mul_as_f64);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    #[test]
+    fn ws_empty() {
+        let result = super::ws("");
+        assert_eq!(result, "");
+    }
+
+    #[test]
     fn ws_space() {
         let result = super::ws("\u{0020}");
         assert_eq!(result, "");
@@ -683,6 +1862,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn json_integer_overflowing_i64_saturates_instead_of_panicking() {
+        match super::parse("99999999999999999999") {
+            Ok(super::JSONValue::Number(n)) => assert!(n.is_finite()),
+            Ok(_) => panic!("Expected a number"),
+            Err(_) => panic!("Expected an overflowing integer to still parse"),
+        }
+    }
+
+    #[test]
+    fn json_exponent_overflowing_i64_saturates_instead_of_panicking() {
+        match super::parse("1e99999999999999999999") {
+            Ok(super::JSONValue::Number(n)) => assert!(n.is_infinite()),
+            Ok(_) => panic!("Expected a number"),
+            Err(_) => panic!("Expected an overflowing exponent to still parse"),
+        }
+    }
+
     #[test]
     fn json_float_positive() {
         match super::parse("123.456") {
@@ -784,6 +1981,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn json_unicode_escape() {
+        match super::parse(r#""caf\u00e9""#) {
+            Ok(v) => assert_eq!(v, super::JSONValue::String("caf\u{00e9}".to_string())),
+            Err(_) => panic!("Expected \"caf\\u00e9\""),
+        }
+    }
+
+    #[test]
+    fn json_unicode_surrogate_pair() {
+        match super::parse(r#""\ud83d\ude00""#) {
+            Ok(v) => assert_eq!(v, super::JSONValue::String("\u{1f600}".to_string())),
+            Err(_) => panic!("Expected surrogate pair to decode to U+1F600"),
+        }
+    }
+
+    #[test]
+    fn json_unicode_lone_surrogate() {
+        assert!(super::parse(r#""\ud83d""#).is_err());
+    }
+
     #[test]
     fn json_empty_list() {
         let src = r#"[]"#;
@@ -797,4 +2015,387 @@ mod tests {
             Err(_) => panic!("Expected []"),
         }
     }
+
+    #[test]
+    fn json_object_preserves_insertion_order() {
+        let src = r#"{"b": 1, "a": 2, "c": 3}"#;
+
+        let expected = super::JSONValue::Object(vec![
+            ("b".to_string(), super::JSONValue::Number(1.0)),
+            ("a".to_string(), super::JSONValue::Number(2.0)),
+            ("c".to_string(), super::JSONValue::Number(3.0)),
+        ]);
+
+        match super::parse(src) {
+            Ok(v) => assert_eq!(v, expected),
+            Err(_) => panic!("Expected an object with b, a, c in that order"),
+        }
+    }
+
+    #[test]
+    fn json_object_duplicate_key_default_keeps_last() {
+        let src = r#"{"a": 1, "a": 2}"#;
+
+        let expected =
+            super::JSONValue::Object(vec![("a".to_string(), super::JSONValue::Number(2.0))]);
+
+        match super::parse(src) {
+            Ok(v) => assert_eq!(v, expected),
+            Err(_) => panic!("Expected duplicate key \"a\" to resolve to the last value"),
+        }
+    }
+
+    #[test]
+    fn json_object_duplicate_key_keep_first() {
+        let src = r#"{"a": 1, "a": 2}"#;
+
+        let expected =
+            super::JSONValue::Object(vec![("a".to_string(), super::JSONValue::Number(1.0))]);
+
+        match super::parse_with_duplicate_key_policy(src, super::DuplicateKeyPolicy::KeepFirst) {
+            Ok(v) => assert_eq!(v, expected),
+            Err(_) => panic!("Expected duplicate key \"a\" to resolve to the first value"),
+        }
+    }
+
+    #[test]
+    fn json_object_duplicate_key_error() {
+        let src = r#"{"a": 1, "a": 2}"#;
+
+        match super::parse_with_duplicate_key_policy(src, super::DuplicateKeyPolicy::Error) {
+            Ok(_) => panic!("Expected a duplicate key error"),
+            Err(super::JSONParseError::DuplicateKey(key)) => assert_eq!(key, "a"),
+            Err(_) => panic!("Expected JSONParseError::DuplicateKey"),
+        }
+    }
+
+    #[test]
+    fn json_object_duplicate_key_keeps_first_occurrence_position() {
+        // "a" is repeated a third time at the end; the resolved value should
+        // land at the position of its first occurrence, not get appended
+        let src = r#"{"a": 1, "b": 2, "a": 3, "c": 4, "a": 5}"#;
+
+        let expected = super::JSONValue::Object(vec![
+            ("a".to_string(), super::JSONValue::Number(5.0)),
+            ("b".to_string(), super::JSONValue::Number(2.0)),
+            ("c".to_string(), super::JSONValue::Number(4.0)),
+        ]);
+
+        assert_eq!(super::parse(src).expect("should parse"), expected);
+    }
+
+    #[test]
+    fn json_deeply_nested_array_exceeds_default_depth() {
+        let src = format!("{}{}", "[".repeat(129), "]".repeat(129));
+
+        match super::parse(&src) {
+            Ok(_) => panic!("Expected a depth exceeded error"),
+            Err(super::JSONParseError::DepthExceeded(depth)) => assert_eq!(depth, 129),
+            Err(_) => panic!("Expected JSONParseError::DepthExceeded"),
+        }
+    }
+
+    #[test]
+    fn json_nesting_within_default_depth_still_parses() {
+        let src = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(super::parse(&src).is_ok());
+    }
+
+    #[test]
+    fn json_custom_max_depth_is_enforced() {
+        let src = r#"{"a": {"b": 1}}"#;
+
+        match super::parse_with_max_depth(src, 1) {
+            Ok(_) => panic!("Expected a depth exceeded error"),
+            Err(super::JSONParseError::DepthExceeded(depth)) => assert_eq!(depth, 2),
+            Err(_) => panic!("Expected JSONParseError::DepthExceeded"),
+        }
+    }
+
+    #[test]
+    fn parse_reader_enforces_max_depth() {
+        let src = format!("{}{}", "[".repeat(129), "]".repeat(129));
+
+        match super::parse_reader(src.as_bytes()) {
+            Ok(_) => panic!("Expected a depth exceeded error"),
+            Err(super::JSONParseError::DepthExceeded(depth)) => assert_eq!(depth, 129),
+            Err(_) => panic!("Expected JSONParseError::DepthExceeded"),
+        }
+    }
+
+    #[test]
+    fn parse_reader_with_max_depth_is_enforced() {
+        let src = r#"{"a": {"b": 1}}"#;
+
+        match super::parse_reader_with_max_depth(src.as_bytes(), 1) {
+            Ok(_) => panic!("Expected a depth exceeded error"),
+            Err(super::JSONParseError::DepthExceeded(depth)) => assert_eq!(depth, 2),
+            Err(_) => panic!("Expected JSONParseError::DepthExceeded"),
+        }
+    }
+
+    // a single, un-nested oversized number literal doesn't trip max_depth
+    // at all (it never recurses), so it has to be safe on its own merits;
+    // this locks in that the depth guard and the overflow guard cover two
+    // distinct DoS vectors, not the same one twice
+    #[test]
+    fn max_depth_does_not_protect_against_numeric_overflow_but_parsing_is_still_safe() {
+        match super::parse_with_max_depth("99999999999999999999", 0) {
+            Ok(super::JSONValue::Number(n)) => assert!(n.is_finite()),
+            Ok(_) => panic!("Expected a number"),
+            Err(_) => panic!("Expected an overflowing integer to still parse"),
+        }
+    }
+
+    #[test]
+    fn json_expr_placeholder_disabled_by_default() {
+        let src = r#"{"a": {{ user.name }}}"#;
+        assert!(super::parse(src).is_err());
+    }
+
+    #[test]
+    fn json_expr_placeholder_parses_when_enabled() {
+        let src = r#"{"a": {{ user.name }}, "b": 1}"#;
+
+        let expected = super::JSONValue::Object(vec![
+            (
+                "a".to_string(),
+                super::JSONValue::Expr("user.name".to_string()),
+            ),
+            ("b".to_string(), super::JSONValue::Number(1.0)),
+        ]);
+
+        assert_eq!(
+            super::parse_with_expr_placeholders(src).expect("should parse"),
+            expected
+        );
+    }
+
+    #[test]
+    fn serialize_roundtrips_expr_placeholder() {
+        let v = super::JSONValue::Array(vec![super::JSONValue::Expr("1 + 1".to_string())]);
+        assert_eq!(super::serialize(&v), "[{{ 1 + 1 }}]");
+    }
+
+    #[test]
+    fn parse_reader_with_expr_placeholders_parses_when_enabled() {
+        let src = r#"{"a": {{ user.name }}}"#;
+
+        let expected = super::JSONValue::Object(vec![(
+            "a".to_string(),
+            super::JSONValue::Expr("user.name".to_string()),
+        )]);
+
+        assert_eq!(
+            super::parse_reader_with_expr_placeholders(src.as_bytes()).expect("should parse"),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_reader_without_expr_placeholders_rejects_them() {
+        let src = r#"{"a": {{ user.name }}}"#;
+        assert!(super::parse_reader(src.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn serialize_compact_roundtrip() {
+        let src = r#"{"b":[1,2.5,true,false,null,"hi"],"a":"café"}"#;
+
+        let v = super::parse(src).expect("should parse");
+        assert_eq!(super::serialize(&v), src);
+    }
+
+    #[test]
+    fn serialize_number_without_spurious_precision() {
+        let v = super::JSONValue::Array(vec![
+            super::JSONValue::Number(123.0),
+            super::JSONValue::Number(123.456),
+        ]);
+
+        assert_eq!(super::serialize(&v), "[123,123.456]");
+    }
+
+    #[test]
+    fn serialize_escapes_control_characters() {
+        let v = super::JSONValue::String("line1\nline2\u{0001}".to_string());
+        assert_eq!(super::serialize(&v), r#""line1\nline2\u0001""#);
+    }
+
+    #[test]
+    fn serialize_pretty_indents_nested_containers() {
+        let v = super::JSONValue::Object(vec![(
+            "a".to_string(),
+            super::JSONValue::Array(vec![super::JSONValue::Number(1.0)]),
+        )]);
+
+        let expected = "{\n  \"a\": [\n    1\n  ]\n}";
+        assert_eq!(super::serialize_pretty(&v, 2), expected);
+    }
+
+    #[test]
+    fn serialize_empty_containers() {
+        assert_eq!(super::serialize(&super::JSONValue::Array(vec![])), "[]");
+        assert_eq!(super::serialize(&super::JSONValue::Object(vec![])), "{}");
+    }
+
+    #[test]
+    fn serialize_non_finite_numbers_as_null() {
+        assert_eq!(
+            super::serialize(&super::JSONValue::Number(f64::INFINITY)),
+            "null"
+        );
+        assert_eq!(
+            super::serialize(&super::JSONValue::Number(f64::NEG_INFINITY)),
+            "null"
+        );
+        assert_eq!(
+            super::serialize(&super::JSONValue::Number(f64::NAN)),
+            "null"
+        );
+    }
+
+    #[test]
+    fn parse_then_serialize_overflowing_number_round_trips_as_valid_json() {
+        // 1e400 overflows f64 to infinity without panicking (see chunk0-4's
+        // saturating integer/exponent parse), so the serializer is the
+        // last line of defense against printing invalid JSON for it
+        let v = super::parse("1e400").expect("should parse");
+        assert_eq!(super::serialize(&v), "null");
+    }
+
+    #[test]
+    fn parse_reader_matches_parse() {
+        let src = r#"{"b": [1, 2.5, true, false, null, "café"], "a": "😀"}"#;
+
+        let expected = super::parse(src).expect("should parse from a &str");
+        let from_reader = super::parse_reader(src.as_bytes()).expect("should parse from a reader");
+
+        assert_eq!(from_reader, expected);
+    }
+
+    #[test]
+    fn parse_reader_missing_closing() {
+        let src = r#"{"a": 1"#;
+        assert!(super::parse_reader(src.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn parse_reader_rejects_invalid_utf8() {
+        // 0xFF is not a valid UTF-8 leading byte anywhere in the spec
+        let src: &[u8] = &[b'"', 0xFF, b'"'];
+
+        match super::parse_reader(src) {
+            Ok(_) => panic!("Expected an IO error for invalid UTF-8"),
+            Err(super::JSONParseError::Io(message)) => assert!(message.contains("UTF-8")),
+            Err(_) => panic!("Expected JSONParseError::Io"),
+        }
+    }
+
+    // a reader that fails with an io::Error on its first read, used to
+    // exercise JSONParseError::Io's non-UTF-8 path (a genuine I/O failure)
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn parse_reader_surfaces_io_errors() {
+        match super::parse_reader(FailingReader) {
+            Ok(_) => panic!("Expected an IO error"),
+            Err(super::JSONParseError::Io(message)) => assert!(message.contains("disk on fire")),
+            Err(_) => panic!("Expected JSONParseError::Io"),
+        }
+    }
+
+    #[test]
+    fn parse_reader_integer_overflowing_i64_saturates_instead_of_panicking() {
+        let src = "99999999999999999999";
+        match super::parse_reader(src.as_bytes()) {
+            Ok(super::JSONValue::Number(n)) => assert!(n.is_finite()),
+            Ok(_) => panic!("Expected a number"),
+            Err(_) => panic!("Expected an overflowing integer to still parse"),
+        }
+    }
+
+    #[test]
+    fn parse_reader_exponent_overflowing_i64_saturates_instead_of_panicking() {
+        let src = "1e99999999999999999999";
+        match super::parse_reader(src.as_bytes()) {
+            Ok(super::JSONValue::Number(n)) => assert!(n.is_infinite()),
+            Ok(_) => panic!("Expected a number"),
+            Err(_) => panic!("Expected an overflowing exponent to still parse"),
+        }
+    }
+
+    #[test]
+    fn parse_reader_duplicate_key_error() {
+        let src = r#"{"a": 1, "a": 2}"#;
+
+        match super::parse_reader_with_duplicate_key_policy(
+            src.as_bytes(),
+            super::DuplicateKeyPolicy::Error,
+        ) {
+            Ok(_) => panic!("Expected a duplicate key error"),
+            Err(super::JSONParseError::DuplicateKey(key)) => assert_eq!(key, "a"),
+            Err(_) => panic!("Expected JSONParseError::DuplicateKey"),
+        }
+    }
+
+    #[test]
+    fn read_canada_json_via_reader() {
+        let file = fs::File::open("canada.json").expect("Should have been able to open the file");
+        match super::parse_reader(file) {
+            Ok(_) => {}
+            Err(_) => panic!("Errored"),
+        }
+    }
+
+    #[test]
+    fn accessors_on_matching_variants() {
+        let v =
+            super::parse(r#"{"a": 1, "b": "hi", "c": true, "d": [1, 2]}"#).expect("should parse");
+
+        assert_eq!(v.get("a").and_then(|v| v.as_number()), Some(1.0));
+        assert_eq!(v.get("b").and_then(|v| v.as_str()), Some("hi"));
+        assert_eq!(v.get("c").and_then(|v| v.as_bool()), Some(true));
+        assert_eq!(
+            v.get("d").and_then(|v| v.as_array()).map(|a| a.len()),
+            Some(2)
+        );
+        assert!(v.get("missing").is_none());
+    }
+
+    #[test]
+    fn accessors_on_mismatched_variants_return_none() {
+        let v = super::JSONValue::Number(1.0);
+
+        assert_eq!(v.as_str(), None);
+        assert_eq!(v.as_bool(), None);
+        assert_eq!(v.as_array(), None);
+        assert_eq!(v.as_object(), None);
+    }
+
+    #[test]
+    fn indexed_array_access() {
+        let v = super::parse("[10, 20, 30]").expect("should parse");
+        assert_eq!(v[1], super::JSONValue::Number(20.0));
+    }
+
+    #[test]
+    fn unwrap_converts_to_target_type() {
+        let v = super::parse(r#""hello""#).expect("should parse");
+        let s: String = v.unwrap();
+        assert_eq!(s, "hello");
+    }
+
+    #[test]
+    fn try_from_rejects_mismatched_variant() {
+        let v = super::JSONValue::True;
+        let result: Result<f64, super::JSONValue> = f64::try_from(v.clone());
+        assert_eq!(result, Err(v));
+    }
 }